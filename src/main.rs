@@ -18,12 +18,14 @@ use std::str::FromStr;
 
 extern crate iron;
 #[macro_use] extern crate mime;
+extern crate urlencoded;
 
 // Bring names from external crates into local namespace
 // Modules named "prelude" are intended to be included using '*'
 // by convention.
 use iron::prelude::*;
 use iron::status;
+use urlencoded::UrlEncodedBody;
 
 
 /// The main function doesn't return a value.
@@ -32,6 +34,19 @@ use iron::status;
 /// functions that aren't supposed to return.)
 fn main()
 {
+    // If the first argument is "--serve", skip the CLI loop below entirely
+    // and hand off to the Iron server instead. An optional second argument
+    // lets you pick the port; otherwise we default to 3000.
+    let mut args = std::env::args().skip(1);
+    if let Some(first) = args.next()
+    {
+        if first == "--serve"
+        {
+            let port = args.next().unwrap_or_else(|| "3000".to_string());
+            return serve(&port);
+        }
+    }
+
     // Page 45 of the crab book has the basic types and literals.
     // Here are a few interesting examples.
     
@@ -89,33 +104,112 @@ fn main()
     // (these look like any other reference to me), and closures (whose type
     // is a secret of the compiler.)
 
-    let mut numbers = Vec::new(); // new is a static factory function of the type.
+    // "--parallel" may appear anywhere among the remaining arguments; strip
+    // it out before handing the rest to parse_numbers() as numbers. Keep
+    // each argument paired with its original (1-based) CLI position so
+    // error messages still point at what the user actually typed, even
+    // though "--parallel" itself is no longer in the list being parsed.
+    let remaining: Vec<String> = std::env::args().skip(1).collect();
+    let force_parallel = remaining.iter().any(|a| a == "--parallel");
+    let numeric_args = remaining.into_iter()
+        .enumerate()
+        .map(|(i, arg)| (i + 1, arg))
+        .filter(|(_, arg)| arg != "--parallel");
 
-    // Raw strings in Rust don't have any escape sequences. The number of #
-    // symbols before the opening " needs to match the number after the closing
-    // quote for the closing quote to be recognised.  It can be any number,
-    // including zero.
-    let msg = r#"Error parsing the argument"#;
-    
-    // std::env::args() produces an iterator.
-    for arg in std::env::args().skip(1)
+    // std::env::args() produces an iterator. parse_numbers() does the
+    // actual work of turning those strings into u64s, and does it without
+    // panicking (see parse_numbers below for why that matters).
+    let numbers = match parse_numbers(numeric_args)
     {
-        // .expect is called on the Result type that is returned by
-        // u64::from_str.
-        // The '&' in &arg means borrow a non-mutable reference
-        numbers.push(u64::from_str(&arg).expect(msg));
-    }
+        Ok(numbers) => numbers,
+        Err(msg) =>
+        {
+            // .unwrap() checks that the write didn't fail. Could have used .expect(msg)
+            writeln!(std::io::stderr(), "{}", msg).unwrap();
+            std::process::exit(1);
+        }
+    };
 
     if numbers.len() == 0
     {
-        // .unwrap() checks that the write didn't fail. Could have used .expect(msg)
         writeln!(std::io::stderr(), "Usage: gcd <UINT>+").unwrap();
         std::process::exit(1);
     }
-    
-    
+
+    // gcd_reduce() picks the sequential fold or the threaded one depending
+    // on list length and the --parallel flag; see its doc comment.
+    let d = gcd_reduce(&numbers, force_parallel);
+
+    println!("The greatest common divisor of {:?} is {}", numbers, d);
+
+    // Use an underscore for a variable name when you don't care about it.
+    let _ = gcd(2u64, 3u64);
+    let _ = extended_gcd(2, 3);
+}
+
+/// Parses every argument as a `u64`, returning them all on success.
+/// Unlike `u64::from_str(&arg).expect(msg)`, this doesn't bail out of the
+/// whole batch at the first bad argument: it keeps going, collects every
+/// offending argument along with its position, and only returns `Err`
+/// once the whole batch has been checked. That way a user who typos
+/// three arguments out of ten finds out about all three in one run instead
+/// of one at a time.
+///
+/// Takes `(position, argument)` pairs rather than bare strings so that
+/// callers who filter out flags (like `--parallel` in `main`) before
+/// parsing can still report the argument's original CLI position instead
+/// of its index into the filtered list.
+fn parse_numbers<I>(args: I) -> Result<Vec<u64>, String>
+    where I: Iterator<Item = (usize, String)>
+{
+    let mut numbers = Vec::new();
+    let mut errors = Vec::new();
+
+    for (position, arg) in args
+    {
+        match u64::from_str(&arg)
+        {
+            Ok(n) => numbers.push(n),
+            Err(_) => errors.push(format!("argument {} (\"{}\")", position, arg)),
+        }
+    }
+
+    if errors.is_empty()
+    {
+        Ok(numbers)
+    }
+    else
+    {
+        Err(format!("Error parsing the argument(s): {}", errors.join(", ")))
+    }
+}
+
+/// Below this many numbers, the sequential fold below is already fast
+/// enough that splitting work across threads would just add overhead.
+const PARALLEL_THRESHOLD: usize = 10_000;
+
+/// Folds `gcd` over `numbers`, sequentially or across worker threads.
+/// Takes the threaded path when `force` is set (the `--parallel` flag) or
+/// the list is long enough that splitting it up is worth the thread
+/// overhead; otherwise it falls back to the plain sequential fold.
+fn gcd_reduce(numbers: &[u64], force: bool) -> u64
+{
+    if force || numbers.len() >= PARALLEL_THRESHOLD
+    {
+        parallel_gcd(numbers)
+    }
+    else
+    {
+        sequential_gcd(numbers)
+    }
+}
+
+/// The original strictly-sequential fold: `d = gcd(d, *m)` for each
+/// remaining number.
+fn sequential_gcd(numbers: &[u64]) -> u64
+{
     let mut d = numbers[0];
-    
+
     // &numbers[1..] is a bit weird.
     // The '&' means borrow a non-mutable reference to...
     // ... in this case each of the values of numbers from
@@ -127,22 +221,178 @@ fn main()
         // Using *m to dereference the borrowed reference.
         d = gcd(d, *m);
     }
-    
-    println!("The greatest common divisor of {:?} is {}", numbers, d);
 
-    // Use an underscore for a variable name when you don't care about it.
-    let _ = gcd(2, 3);
+    d
+}
+
+/// Splits `numbers` into one chunk per available CPU, computes the
+/// sequential gcd of each chunk on its own thread, then folds the partial
+/// results together. This is sound because gcd is associative and
+/// commutative, so `gcd(gcd(a, b), gcd(c, d)) == gcd(a, b, c, d)`
+/// regardless of how the numbers are grouped.
+///
+/// `std::thread::scope` lets the worker closures borrow `numbers`
+/// directly instead of needing to clone chunks into owned `Vec`s.
+fn parallel_gcd(numbers: &[u64]) -> u64
+{
+    let worker_count = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(numbers.len());
+    let chunk_size = numbers.len().div_ceil(worker_count);
+
+    let partials: Vec<u64> = std::thread::scope(|scope| {
+        let handles: Vec<_> = numbers
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || sequential_gcd(chunk)))
+            .collect();
+
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    sequential_gcd(&partials)
+}
+
+/// Starts an Iron server bound to `127.0.0.1:<port>`. Requests are routed
+/// by method only, whatever their path: a GET serves the form (which posts
+/// to "/gcd"), a POST reads it back and computes the answer, and any other
+/// method gets `MethodNotAllowed`.
+fn serve(port: &str)
+{
+    let addr = format!("127.0.0.1:{}", port);
+    println!("Serving on http://{}...", addr);
+    Iron::new(|request: &mut Request| {
+        match request.method
+        {
+            iron::method::Method::Get => get_form(request),
+            iron::method::Method::Post => post_gcd(request),
+            _ => Ok(Response::with(status::MethodNotAllowed)),
+        }
+    }).http(&addr[..]).unwrap();
+}
+
+/// Renders the HTML page with the two-number form. Iron requires every
+/// handler to be `Fn`, so this takes `&mut Request` even though it never
+/// reads the request body.
+fn get_form(_request: &mut Request) -> IronResult<Response>
+{
+    let mut response = Response::new();
+
+    response.set_mut(status::Ok);
+    response.set_mut(mime!(Text/Html; Charset=Utf8));
+    response.set_mut(r#"
+        <title>GCD calculator</title>
+        <form action="/gcd" method="post">
+            <input type="text" name="n"/>
+            <input type="text" name="m"/>
+            <button type="submit">Compute GCD</button>
+        </form>
+    "#);
+
+    Ok(response)
+}
+
+/// Looks up `name` in the posted form and parses its first value as a
+/// `u64`. Factored out of `post_gcd` because "n" and "m" need exactly the
+/// same missing-field/missing-value/bad-number handling, and that's the
+/// bulk of what this file's "--serve" code does.
+fn parse_field(form: &urlencoded::QueryMap, name: &str) -> Result<u64, Response>
+{
+    let unparsed_numbers = match form.get(name)
+    {
+        None =>
+        {
+            let msg = format!(r#"form data has no "{}" parameter"#, name);
+            return Err(Response::with((status::BadRequest, msg)));
+        }
+        Some(nums) => nums,
+    };
+
+    match unparsed_numbers.first()
+    {
+        None =>
+        {
+            let msg = format!(r#""{}" parameter had no values"#, name);
+            Err(Response::with((status::BadRequest, msg)))
+        }
+        Some(value) => match u64::from_str(value)
+        {
+            Ok(n) => Ok(n),
+            Err(_) =>
+            {
+                let msg = format!(r#""{}" parameter is not a number"#, name);
+                Err(Response::with((status::BadRequest, msg)))
+            }
+        }
+    }
+}
+
+/// Parses the "n" and "m" fields out of the posted form, computes their
+/// GCD and renders it. Error messages are raw strings: any missing field
+/// or parse failure produces a `BadRequest` carrying a message saying
+/// exactly what went wrong.
+fn post_gcd(request: &mut Request) -> IronResult<Response>
+{
+    let form_data = match request.get_ref::<UrlEncodedBody>()
+    {
+        Err(e) =>
+        {
+            let msg = r#"Error parsing form data"#;
+            println!("{}: {:?}", msg, e);
+            return Ok(Response::with((status::BadRequest, msg)));
+        }
+        Ok(map) => map,
+    };
+
+    let n = match parse_field(form_data, "n")
+    {
+        Ok(n) => n,
+        Err(response) => return Ok(response),
+    };
+    let m = match parse_field(form_data, "m")
+    {
+        Ok(m) => m,
+        Err(response) => return Ok(response),
+    };
+
+    let mut response = Response::new();
+    response.set_mut(status::Ok);
+    response.set_mut(mime!(Text/Html; Charset=Utf8));
+    response.set_mut(format!("The greatest common divisor of {} and {} is <b>{}</b>\n", n, m, gcd(n, m)));
+    Ok(response)
+}
+
+/// The set of operations `gcd` actually needs from an integer type: a zero
+/// value to test against, ordering (to decide which operand is smaller) and
+/// remainder. Every unsigned integer type in the standard library satisfies
+/// this, so implementing it is just a one-liner per type below.
+trait Integer: Copy + PartialEq + PartialOrd + std::ops::Rem<Output = Self>
+{
+    fn zero() -> Self;
+}
+
+macro_rules! impl_integer
+{
+    ($($t:ty)*) => ($(
+        impl Integer for $t
+        {
+            fn zero() -> Self { 0 }
+        }
+    )*)
 }
 
+impl_integer! { u32 u64 u128 usize }
+
 /// fn introduces a function
 /// mut is the keyword that means unconst. If it is missing, the variable is a constant.
-/// u64 is an unsigned 64bit integer.
+/// Generic over any `Integer` (u32, u64, u128 and usize), so callers no
+/// longer need to cast their values down to u64 just to take a gcd.
 /// Use usize for an unsigned integer the same size as a pointer.
-fn gcd (mut n: u64, mut m: u64) -> u64
+fn gcd<T: Integer> (mut n: T, mut m: T) -> T
 {
     // assert! is a macro. The exclamation mark indicates the "macroness".
-    assert!(n != 0 && m != 0); // semicolons mean something
-    while m != 0
+    assert!(n != T::zero() && m != T::zero()); // semicolons mean something
+    while m != T::zero()
     {
         if m < n
         {
@@ -155,13 +405,146 @@ fn gcd (mut n: u64, mut m: u64) -> u64
     n // Missing the semicolon here means that the return value of this function is the value of n.
 }
 
+/// The extended Euclidean algorithm: alongside the gcd `g` of `a` and `b`,
+/// it produces Bezout coefficients `x` and `y` such that `a*x + b*y == g`.
+/// That's exactly what you need to compute modular inverses or solve
+/// linear Diophantine equations, neither of which the plain `gcd` above
+/// gives you.
+///
+/// Kept separate from the generic `gcd` rather than folded into the
+/// `Integer` trait: the coefficient recurrences need signed arithmetic
+/// even though `a` and `b` are `u64`.
+///
+/// The recurrence itself runs in `i128`, not `i64`: `a` and `b` can be as
+/// large as `u64::MAX`, which overflows `i64` as soon as it's cast, so an
+/// `i64` recurrence silently wraps and returns the wrong gcd for roughly
+/// half of the `u64` domain. `i128` has headroom for the full `u64` range
+/// plus the recurrence's intermediate products. The returned coefficients
+/// narrow to `i64` because the algorithm keeps them small: apart from the
+/// trivial cases where they are 0 or 1, `|x| <= b / (2*g)` and
+/// `|y| <= a / (2*g)` (a standard property of the algorithm), and
+/// `u64::MAX / 2` is exactly `i64::MAX`.
+fn extended_gcd(a: u64, b: u64) -> (u64, i64, i64)
+{
+    assert!(a != 0 || b != 0);
+
+    let (mut old_r, mut r) = (a as i128, b as i128);
+    let (mut old_s, mut s) = (1i128, 0i128);
+    let (mut old_t, mut t) = (0i128, 1i128);
+
+    while r != 0
+    {
+        let q = old_r / r;
+
+        let new_r = old_r - q * r;
+        old_r = r;
+        r = new_r;
+
+        let new_s = old_s - q * s;
+        old_s = s;
+        s = new_s;
+
+        let new_t = old_t - q * t;
+        old_t = t;
+        t = new_t;
+    }
+
+    (old_r as u64, old_s as i64, old_t as i64)
+}
+
 // The "#[test]" is an example of an attribute.
 #[test]
 fn test_gcd()
 {
-    assert_eq!(gcd(14, 15), 1);
-    
-    assert_eq!(gcd(2 * 3 * 5 * 11 * 17,
-                   3 * 7 * 11 * 13 * 19),
+    assert_eq!(gcd(14u64, 15u64), 1);
+
+    assert_eq!(gcd(2 * 3 * 5 * 11 * 17u64,
+                   3 * 7 * 11 * 13 * 19u64),
+               3 * 11);
+}
+
+// Same algorithm, different widths, to prove `gcd` is genuinely generic
+// rather than accidentally inferring u64 everywhere above.
+#[test]
+fn test_gcd_u32()
+{
+    assert_eq!(gcd(14u32, 15u32), 1);
+
+    assert_eq!(gcd(2 * 3 * 5 * 11 * 17u32,
+                   3 * 7 * 11 * 13 * 19u32),
+               3 * 11);
+}
+
+#[test]
+fn test_gcd_u128()
+{
+    assert_eq!(gcd(14u128, 15u128), 1);
+
+    assert_eq!(gcd(2 * 3 * 5 * 11 * 17u128,
+                   3 * 7 * 11 * 13 * 19u128),
                3 * 11);
+}
+
+#[test]
+fn test_parse_numbers()
+{
+    let good = vec!["14".to_string(), "15".to_string()];
+    assert_eq!(parse_numbers(good.into_iter().enumerate().map(|(i, a)| (i + 1, a))),
+               Ok(vec![14, 15]));
+
+    let bad = vec!["14".to_string(), "oops".to_string(), "nope".to_string()];
+    let err = parse_numbers(bad.into_iter().enumerate().map(|(i, a)| (i + 1, a))).unwrap_err();
+    assert!(err.contains("argument 2 (\"oops\")"));
+    assert!(err.contains("argument 3 (\"nope\")"));
+}
+
+// Regression test: once "--parallel" is filtered out before parsing, the
+// remaining bad argument's reported position must still be its original
+// CLI position, not its index into the filtered list.
+#[test]
+fn test_parse_numbers_reports_original_position_around_flag()
+{
+    let argv = vec!["5".to_string(), "--parallel".to_string(), "oops".to_string()];
+    let numeric_args = argv.into_iter()
+        .enumerate()
+        .map(|(i, arg)| (i + 1, arg))
+        .filter(|(_, arg)| arg != "--parallel");
+
+    let err = parse_numbers(numeric_args).unwrap_err();
+    assert!(err.contains("argument 3 (\"oops\")"));
+}
+
+#[test]
+fn test_parallel_gcd_agrees_with_sequential()
+{
+    // A large generated vector of the multiples 84, 168, 252, ... of 84,
+    // long enough to be split across several worker threads. This checks
+    // the two reduction strategies against each other, not against a
+    // hand-computed answer.
+    let numbers: Vec<u64> = (0..50_000).map(|i| 84 * (i + 1)).collect();
+
+    assert_eq!(sequential_gcd(&numbers), parallel_gcd(&numbers));
+}
+
+#[test]
+fn test_extended_gcd()
+{
+    for &(a, b) in &[(14u64, 15u64), (35, 10), (240, 46), (17, 13), (1, 1)]
+    {
+        let (g, x, y) = extended_gcd(a, b);
+        assert_eq!(g, gcd(a, b));
+        assert_eq!(a as i64 * x + b as i64 * y, g as i64);
+    }
+}
+
+// Regression test: `a` here is well above i64::MAX, which used to wrap
+// silently once cast down to i64 and produce the wrong gcd.
+#[test]
+fn test_extended_gcd_near_u64_max()
+{
+    let (a, b) = (u64::MAX - 5, 12345u64);
+
+    let (g, x, y) = extended_gcd(a, b);
+    assert_eq!(g, gcd(a, b));
+    assert_eq!(a as i128 * x as i128 + b as i128 * y as i128, g as i128);
 }
\ No newline at end of file